@@ -0,0 +1,182 @@
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+/// Parses `instructions.in` and emits the `operators` table, the
+/// `OperandType` enum, and their `Rule` literals into `$OUT_DIR/operators.rs`,
+/// which `asm::line` pulls in with `include!`.
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let spec_path = Path::new(&manifest_dir).join("instructions.in");
+    println!("cargo:rerun-if-changed={}", spec_path.display());
+
+    let spec = fs::read_to_string(&spec_path).expect("failed to read instructions.in");
+    let operators = parse_spec(&spec);
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let out_path = Path::new(&out_dir).join("operators.rs");
+    fs::write(out_path, render(&operators)).expect("failed to write generated operators table");
+}
+
+struct ParsedOperator {
+    mnemonic: String,
+    operands: Vec<String>,
+    opecode: Vec<String>,
+    rex: &'static str,
+    modrm: String,
+    imm: &'static str,
+}
+
+fn parse_spec(spec: &str) -> Vec<ParsedOperator> {
+    let mut operators = Vec::new();
+
+    for line in spec.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (signature, encoding) = line
+            .split_once('=')
+            .unwrap_or_else(|| panic!("expected `sig = encoding` line, got: {line}"));
+
+        let mut signature = signature.trim().splitn(2, char::is_whitespace);
+        let mnemonic = signature.next().expect("expected mnemonic").to_string();
+        let operands: Vec<String> = signature
+            .next()
+            .unwrap_or("none")
+            .split(',')
+            .map(|operand| operand.trim().to_string())
+            .filter(|operand| operand != "none")
+            .collect();
+
+        let mut rex = "None";
+        let mut modrm = "None".to_string();
+        let mut imm = "None";
+        let mut opecode = Vec::new();
+
+        for token in encoding.split_whitespace() {
+            match token {
+                "REX.W" => rex = "RexW",
+                "REX" => rex = "Rex",
+                "/r" => modrm = "R".to_string(),
+                "ib" => imm = "Ib",
+                "iw" => imm = "Iw",
+                "id" => imm = "Id",
+                "io" => imm = "Io",
+                "rel32" => imm = "Rel32",
+                "rel8" => imm = "Rel8",
+                token if token.starts_with('/') => modrm = format!("Digit({})", &token[1..]),
+                token => opecode.push(token.to_string()),
+            }
+        }
+
+        operators.push(ParsedOperator { mnemonic, operands, opecode, rex, modrm, imm });
+    }
+
+    operators
+}
+
+fn operand_variant(token: &str) -> &'static str {
+    match token {
+        "r64" => "Reg64",
+        "rm64" => "Rm64",
+        "imm64" => "Imm64",
+        "rel32" => "Rel32",
+        other => panic!("unknown operand token: {other}"),
+    }
+}
+
+fn operand_variants(operators: &[ParsedOperator]) -> Vec<&'static str> {
+    let mut variants = Vec::new();
+    for operator in operators {
+        for operand in &operator.operands {
+            let variant = operand_variant(operand);
+            if !variants.contains(&variant) {
+                variants.push(variant);
+            }
+        }
+    }
+    variants
+}
+
+fn render_operands(operator: &ParsedOperator) -> String {
+    let mut variants: Vec<&str> = operator.operands.iter().map(|operand| operand_variant(operand)).collect();
+    while variants.len() < 2 {
+        variants.push("None");
+    }
+    variants.iter().map(|variant| format!("OperandType::{variant}")).collect::<Vec<_>>().join(", ")
+}
+
+fn render_opecode(tokens: &[String]) -> String {
+    let mut bytes = Vec::new();
+    for token in tokens {
+        let hex = token.split('+').next().unwrap();
+        bytes.push(u8::from_str_radix(hex, 16).unwrap_or_else(|_| panic!("invalid opcode byte: {token}")));
+    }
+    while bytes.len() < 3 {
+        bytes.push(0);
+    }
+    format!("SVec::value([0x{:02x}, 0x{:02x}, 0x{:02x}], {})", bytes[0], bytes[1], bytes[2], tokens.len())
+}
+
+fn render_add_reg(tokens: &[String]) -> &'static str {
+    for token in tokens {
+        if let Some((_, suffix)) = token.split_once('+') {
+            return match suffix {
+                "rb" => "Rb",
+                "rw" => "Rw",
+                "rd" => "Rd",
+                "ro" => "Ro",
+                other => panic!("unknown add-reg suffix: {other}"),
+            };
+        }
+    }
+    "None"
+}
+
+fn render(operators: &[ParsedOperator]) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "// @generated by build.rs from instructions.in. Do not edit by hand.").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "#[derive(Clone, Copy, Default, Debug)]").unwrap();
+    writeln!(out, "pub enum OperandType {{").unwrap();
+    writeln!(out, "    #[default]").unwrap();
+    writeln!(out, "    None,").unwrap();
+    for variant in operand_variants(operators) {
+        writeln!(out, "    {variant},").unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+
+    // `operators` (not `OPERATORS`) matches the lowercase name the
+    // hand-written table it replaces used, and everything downstream
+    // already refers to it that way.
+    writeln!(out, "#[allow(non_upper_case_globals)]").unwrap();
+    writeln!(out, "pub static operators: &[Operator] = &[").unwrap();
+    for operator in operators {
+        writeln!(out, "    Operator {{").unwrap();
+        writeln!(out, "        mnemonic: {:?},", operator.mnemonic).unwrap();
+        writeln!(
+            out,
+            "        operands: SVec::value([{}], {}),",
+            render_operands(operator),
+            operator.operands.len()
+        )
+        .unwrap();
+        writeln!(out, "        encoding_rule: Rule {{").unwrap();
+        writeln!(out, "            opecode: {},", render_opecode(&operator.opecode)).unwrap();
+        writeln!(out, "            rex: RexRule::{},", operator.rex).unwrap();
+        writeln!(out, "            modrm: ModRmRule::{},", operator.modrm).unwrap();
+        writeln!(out, "            imm: ImmRule::{},", operator.imm).unwrap();
+        writeln!(out, "            add_reg: AddRegRule::{},", render_add_reg(&operator.opecode)).unwrap();
+        writeln!(out, "        }},").unwrap();
+        writeln!(out, "    }},").unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    out
+}
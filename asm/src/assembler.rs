@@ -0,0 +1,111 @@
+use crate::encoder::Imm;
+use crate::line::Line;
+use util::error::AsmError;
+use util::svec::SVec;
+
+/// Maximum number of lines a single assembly pass can track; `SVec` is
+/// stack-only, so everything is bounded up front.
+pub const MAX_LINES: usize = 256;
+
+/// Maximum size, in bytes, of a single assembled program.
+pub const MAX_PROGRAM_BYTES: usize = 4096;
+
+/// Two-pass assembler: first walks `lines` computing each instruction's
+/// byte offset (via `Encoder::encode().len()`) and records every
+/// `label -> offset` pair, then encodes each line and patches any
+/// `Rel8`/`Rel32` operand's displacement against the label it references.
+/// The displacement is relative to the *end* of the referencing
+/// instruction, so its length must be known before the delta can be
+/// computed; that's exactly what the first pass provides.
+pub fn assemble<'a>(lines: &[Line<'a>]) -> Result<SVec<MAX_PROGRAM_BYTES, u8>, AsmError<'a>> {
+    let mut offsets: SVec<MAX_LINES, u32> = SVec::new();
+    let mut labels: SVec<MAX_LINES, (&'a str, u32)> = SVec::new();
+
+    let mut offset = 0u32;
+    for line in lines {
+        offsets.try_push(offset)?;
+        if let Some(label) = line.label() {
+            labels.try_push((label, offset))?;
+        }
+        offset += line.encoder()?.map(|encoder| encoder.encode().len() as u32).unwrap_or(0);
+    }
+
+    let mut binary: SVec<MAX_PROGRAM_BYTES, u8> = SVec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let Some(mut encoder) = line.encoder()? else {
+            continue;
+        };
+        let instruction_offset = offsets[i];
+        let instruction_len = encoder.encode().len() as u32;
+
+        if let Some(label) = line.label_ref() {
+            let target_offset = labels
+                .iter()
+                .find(|(candidate, _)| *candidate == label)
+                .map(|(_, offset)| *offset)
+                .ok_or(AsmError::UndefinedLabel(label))?;
+
+            let delta = target_offset as i64 - (instruction_offset + instruction_len) as i64;
+
+            encoder.imm = match encoder.imm {
+                Imm::Imm32(_) => Imm::Imm32(delta as i32 as u32),
+                Imm::Imm8(_) => {
+                    let delta = i8::try_from(delta).map_err(|_| AsmError::ImmediateOutOfRange(label))?;
+                    Imm::Imm8(delta as u8)
+                }
+                imm => imm,
+            };
+        }
+
+        for byte in encoder.encode() {
+            binary.try_push(byte)?;
+        }
+    }
+
+    Ok(binary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line::{operators, RowLine};
+
+    fn line<'a>(label: Option<&'a str>, mnemonic: &'a str, operand: Option<&'a str>) -> Line<'a> {
+        let mut operands = SVec::new();
+        if let Some(operand) = operand {
+            operands.push(operand);
+        }
+        RowLine::new(label, Some(mnemonic), operands).to_line(operators).expect("known mnemonic")
+    }
+
+    #[test]
+    fn resolves_a_forward_rel32_label_to_a_zero_delta() {
+        // jmp end; end: ret -- "end" sits immediately after the jmp, so
+        // its rel32 delta (relative to the end of the jmp) is 0
+        let lines = [line(None, "jmp", Some("end")), line(Some("end"), "ret", None)];
+
+        let binary = assemble(&lines).expect("should assemble");
+        assert_eq!(&*binary, &[0xe9, 0x00, 0x00, 0x00, 0x00, 0xc3]);
+    }
+
+    #[test]
+    fn undefined_label_is_an_error() {
+        let lines = [line(None, "jmp", Some("nowhere"))];
+
+        let err = assemble(&lines).unwrap_err();
+        assert!(matches!(err, AsmError::UndefinedLabel("nowhere")));
+    }
+
+    #[test]
+    fn rel8_delta_out_of_range_is_an_error() {
+        // jmp8 far; 149 rets; far: ret -- puts "far" well past what a
+        // single signed byte can reach from the end of the 2-byte jmp8
+        const RET_COUNT: usize = 150;
+        let mut lines = [line(None, "ret", None); RET_COUNT + 1];
+        lines[0] = line(None, "jmp8", Some("far"));
+        lines[RET_COUNT] = line(Some("far"), "ret", None);
+
+        let err = assemble(&lines).unwrap_err();
+        assert!(matches!(err, AsmError::ImmediateOutOfRange("far")));
+    }
+}
@@ -1,4 +1,5 @@
-use std::ops::{Deref, DerefMut};
+use core::ops::{Deref, DerefMut};
+use crate::line::{find_operator, ImmRule, ModRmRule};
 use util::svec::SVec;
 
 #[derive(Clone, Copy, Debug)]
@@ -6,7 +7,7 @@ use util::svec::SVec;
 /// This is the most low-layer module in asm crate.
 /// # Examples
 /// ```
-/// use asm::encoder::Encoder;
+/// use asm::encoder::{Encoder, Imm};
 /// let mut encoder = Encoder::new();
 ///
 /// encoder.rex_prefix.enable();
@@ -100,6 +101,214 @@ impl Encoder {
 
         binary
     }
+
+    /// decode machine language into an Encoder
+    /// returns the decoded Encoder and the number of bytes it consumed, or
+    /// `None` if `bytes` is truncated or its opcode matches no entry of the
+    /// `operators` table
+    pub fn decode(bytes: &[u8]) -> Option<(Encoder, usize)> {
+        let mut cursor = 0;
+        let mut encoder = Encoder::new();
+
+        // prefix
+            // unsupported
+
+        // rex prefix
+        if let Some(&byte) = bytes.get(cursor) {
+            if byte & 0xf0 == 0x40 {
+                encoder.rex_prefix.enable();
+                encoder.rex_prefix.set_w(byte & 0b1000 != 0);
+                encoder.rex_prefix.set_r(byte & 0b0100 != 0);
+                encoder.rex_prefix.set_x(byte & 0b0010 != 0);
+                encoder.rex_prefix.set_b(byte & 0b0001 != 0);
+                cursor += 1;
+            }
+        }
+
+        // opecode
+        match *bytes.get(cursor)? {
+            0x0f => {
+                encoder.opecode.push(0x0f);
+                cursor += 1;
+                if let escape @ (0x38 | 0x3a) = *bytes.get(cursor)? {
+                    encoder.opecode.push(escape);
+                    cursor += 1;
+                }
+                encoder.opecode.push(*bytes.get(cursor)?);
+                cursor += 1;
+            }
+            byte => {
+                encoder.opecode.push(byte);
+                cursor += 1;
+            }
+        }
+
+        let rule = find_operator(&encoder.opecode)?.encoding_rule();
+
+        // mod_rm
+        if !matches!(rule.modrm, ModRmRule::None) {
+            let byte = *bytes.get(cursor)?;
+            cursor += 1;
+
+            let r#mod = (byte >> 6) & 0b11;
+            let rm = byte & 0b111;
+
+            let mut mod_rm = ModRm::new();
+            mod_rm.set_mod(r#mod);
+            mod_rm.set_reg((byte >> 3) & 0b111);
+            mod_rm.set_rm(rm);
+            encoder.mod_rm = Some(mod_rm);
+
+            // sib
+            if rm == 0b100 && r#mod != 0b11 {
+                let sib_byte = *bytes.get(cursor)?;
+                cursor += 1;
+
+                let mut sib = Sib::new();
+                sib.set_scale((sib_byte >> 6) & 0b11);
+                sib.set_index((sib_byte >> 3) & 0b111);
+                sib.set_base(sib_byte & 0b111);
+                encoder.sib = Some(sib);
+            }
+
+            // disp
+            encoder.disp = if r#mod == 0b00 && rm == 0b101 {
+                let mut disp = 0u32;
+                for i in 0..4 { disp |= (*bytes.get(cursor + i)? as u32) << (i * 8); }
+                cursor += 4;
+                Disp::Disp32(disp)
+            } else if r#mod == 0b01 {
+                let disp = *bytes.get(cursor)?;
+                cursor += 1;
+                Disp::Disp8(disp)
+            } else if r#mod == 0b10 {
+                let mut disp = 0u32;
+                for i in 0..4 { disp |= (*bytes.get(cursor + i)? as u32) << (i * 8); }
+                cursor += 4;
+                Disp::Disp32(disp)
+            } else {
+                Disp::None
+            };
+        }
+
+        // imm
+        encoder.imm = match rule.imm {
+            ImmRule::None => Imm::None,
+            ImmRule::Ib => {
+                let imm = *bytes.get(cursor)?;
+                cursor += 1;
+                Imm::Imm8(imm)
+            }
+            ImmRule::Iw => {
+                let mut imm = 0u16;
+                for i in 0..2 { imm |= (*bytes.get(cursor + i)? as u16) << (i * 8); }
+                cursor += 2;
+                Imm::Imm16(imm)
+            }
+            ImmRule::Id => {
+                let mut imm = 0u32;
+                for i in 0..4 { imm |= (*bytes.get(cursor + i)? as u32) << (i * 8); }
+                cursor += 4;
+                Imm::Imm32(imm)
+            }
+            ImmRule::Io => {
+                let mut imm = 0u64;
+                for i in 0..8 { imm |= (*bytes.get(cursor + i)? as u64) << (i * 8); }
+                cursor += 8;
+                Imm::Imm64(imm)
+            }
+            ImmRule::Rel8 => {
+                let imm = *bytes.get(cursor)?;
+                cursor += 1;
+                Imm::Imm8(imm)
+            }
+            ImmRule::Rel32 => {
+                let mut imm = 0u32;
+                for i in 0..4 { imm |= (*bytes.get(cursor + i)? as u32) << (i * 8); }
+                cursor += 4;
+                Imm::Imm32(imm)
+            }
+        };
+
+        Some((encoder, cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_round_trips_a_no_operand_instruction() {
+        // ret = C3
+        let (decoded, len) = Encoder::decode(&[0xc3]).expect("should decode");
+        assert_eq!(len, 1);
+        assert_eq!(&*decoded.encode(), &[0xc3]);
+    }
+
+    #[test]
+    fn decode_round_trips_an_add_reg_instruction() {
+        // push r64 = 50+rd, register folded into the opcode's low 3 bits
+        let (decoded, len) = Encoder::decode(&[0x50]).expect("should decode");
+        assert_eq!(len, 1);
+        assert_eq!(&*decoded.encode(), &[0x50]);
+    }
+
+    #[test]
+    fn decode_round_trips_a_register_direct_modrm_instruction() {
+        // mov rdx, rax = REX.W 8B /r, mod=11/reg=rdx(2)/rm=rax(0)
+        let bytes = [0x48, 0x8b, 0xd0];
+        let (decoded, len) = Encoder::decode(&bytes).expect("should decode");
+        assert_eq!(len, 3);
+        assert_eq!(&*decoded.encode(), &bytes);
+    }
+
+    #[test]
+    fn decode_round_trips_a_sib_disp32_instruction() {
+        // mov r64, rm64 = REX.W 8B /r, mod=10/reg=0/rm=100 (SIB follows),
+        // sib: scale=00/index=rcx(1)/base=rdx(2), disp32=16
+        let bytes = [0x48, 0x8b, 0x84, 0x0a, 0x10, 0x00, 0x00, 0x00];
+        let (decoded, len) = Encoder::decode(&bytes).expect("should decode");
+        assert_eq!(len, 8);
+        assert_eq!(&*decoded.encode(), &bytes);
+    }
+
+    #[test]
+    fn decode_round_trips_a_rip_relative_instruction() {
+        // mov r64, rm64 = REX.W 8B /r, mod=00/reg=rbx(3)/rm=101 (RIP-relative),
+        // disp32=-8
+        let bytes = [0x48, 0x8b, 0x1d, 0xf8, 0xff, 0xff, 0xff];
+        let (decoded, len) = Encoder::decode(&bytes).expect("should decode");
+        assert_eq!(len, 7);
+        assert_eq!(&*decoded.encode(), &bytes);
+    }
+
+    #[test]
+    fn decode_round_trips_a_mov_r64_imm64_instruction() {
+        // mov rax, imm64 = REX.W B8+rd io; the immediate is 8 bytes (io), not
+        // 4 (id) -- a truncated decode here would leave the cursor short and
+        // corrupt every instruction that follows
+        let bytes = [0x48, 0xb8, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11];
+        let (decoded, len) = Encoder::decode(&bytes).expect("should decode");
+        assert_eq!(len, 10);
+        assert_eq!(&*decoded.encode(), &bytes);
+    }
+
+    #[test]
+    fn decode_rejects_a_truncated_immediate() {
+        // jmp rel32 = E9 rel32 needs 4 more bytes; only 2 are present
+        assert!(Encoder::decode(&[0xe9, 0x01, 0x02]).is_none());
+    }
+
+    #[test]
+    fn decode_rejects_an_empty_input() {
+        assert!(Encoder::decode(&[]).is_none());
+    }
+
+    #[test]
+    fn decode_rejects_an_unknown_opcode() {
+        assert!(Encoder::decode(&[0xff]).is_none());
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -0,0 +1,9 @@
+#![no_std]
+
+//! `asm` is an x64 encoder/decoder and line-level assembler.
+
+pub mod assembler;
+pub mod encoder;
+pub mod line;
+pub mod ml_gen;
+pub mod registers;
@@ -0,0 +1,17 @@
+//! recognizing the small operand grammars (`r64`, `rm64` memory refs) out
+//! of a raw operand token
+
+use util::functions::get_inner_expr;
+
+/// if `operand` (once trimmed) is a bare 64-bit register name, return it
+pub(crate) fn get_reg64_str(operand: &str) -> Option<&str> {
+    let operand = operand.trim();
+    super::REGISTER_NAMES.contains(&operand).then_some(operand)
+}
+
+/// if `operand` is a `[...]` memory reference, return its inner expression
+/// (e.g. `"rax + 8"` out of `"[rax + 8]"`), unstripped of whitespace between
+/// its `+`-separated terms
+pub(crate) fn get_rm64_ref_str(operand: &str) -> Option<&str> {
+    get_inner_expr(operand, '[', ']')
+}
@@ -1,10 +1,9 @@
-use super::ml_gen::*;
-use super::*;
+use crate::encoder::{Disp, Encoder, Imm, ModRm, Opecode, Sib};
 use crate::ml_gen::raw_encoder::{ModRmMode, RexMode};
 use crate::registers::Register;
 use line_parser::{get_reg64_str, get_rm64_ref_str};
+use util::error::AsmError;
 use util::functions::stoi;
-use util::functions::{get_inner_expr, match_str, MatchStr};
 use util::svec::SVec;
 
 mod line_parser;
@@ -16,20 +15,303 @@ pub struct Line<'a> {
 }
 
 impl<'a> Line<'a> {
-    /*
-    pub fn opecode(&self) -> Option<SVec<3, u8>> {
-        Some(self.ops?.0.encoding_rule.opecode)
+    /// the label this line defines, if any
+    pub fn label(&self) -> Option<&'a str> {
+        self.label
     }
 
-    pub fn rex_mode(&self) -> Option<RexMode> {
-        Some(self.ops?.0.encoding_rule.rex)
+    /// the operand, if any, that refers to a label (only `Rel32` operands
+    /// do); used by `crate::assembler` to find what it must relocate
+    pub(crate) fn label_ref(&self) -> Option<&'a str> {
+        let (operator, operands) = self.ops?;
+        for (i, operand_type) in operator.operands.iter().enumerate() {
+            if matches!(operand_type, OperandType::Rel32) {
+                return Some(operands[i]);
+            }
+        }
+        None
+    }
+
+    /// build the `Encoder` for this line's operator and operands, if any
+    /// # Caution
+    /// - a `Rel32` operand is encoded as `Imm::Imm32(0)` (or `Imm::Imm8(0)`
+    ///   when the rule's immediate is `Rel8`); the real displacement is
+    ///   patched in later by `crate::assembler`
+    pub(crate) fn encoder(&self) -> Result<Option<Encoder>, AsmError<'a>> {
+        let Some((operator, operands)) = self.ops else {
+            return Ok(None);
+        };
+        let rule = operator.encoding_rule();
+        let mut encoder = Encoder::new();
+
+        encoder.opecode.set(rule.opecode);
+        match rule.rex {
+            RexMode::RexW => {
+                encoder.rex_prefix.enable();
+                encoder.rex_prefix.set_w(true);
+            }
+            RexMode::Rex => encoder.rex_prefix.enable(),
+            RexMode::None => {}
+        }
+
+        for (i, operand_type) in operator.operands.iter().enumerate() {
+            let operand = operands[i];
+            let mismatch = |expected| AsmError::OperandTypeMismatch { position: i, expected, found: operand };
+
+            match operand_type {
+                OperandType::Reg64 => {
+                    let index = register_index(get_reg64_str(operand).ok_or_else(|| mismatch("r64"))?)
+                        .ok_or_else(|| mismatch("r64"))?;
+                    if index >= 8 {
+                        encoder.rex_prefix.enable();
+                    }
+                    match rule.modrm {
+                        // no ModRM: the register is folded into the
+                        // opcode's low 3 bits (push/pop/mov r64, imm64)
+                        ModRmRule::None => {
+                            let byte = encoder.opecode.pop();
+                            encoder.opecode.push(byte + (index & 0b111));
+                            encoder.rex_prefix.set_b(index >= 8);
+                        }
+                        // ModRM present: this operand fills the reg field
+                        // (e.g. the destination of mov r64, rm64)
+                        _ => {
+                            let mod_rm = encoder.mod_rm.get_or_insert_with(ModRm::new);
+                            mod_rm.set_reg(index & 0b111);
+                            encoder.rex_prefix.set_r(index >= 8);
+                        }
+                    }
+                }
+                OperandType::Rm64 => {
+                    if let Some(name) = get_reg64_str(operand) {
+                        // register-direct form: mod=11, rm=register
+                        let index = register_index(name).ok_or_else(|| mismatch("rm64"))?;
+                        if index >= 8 {
+                            encoder.rex_prefix.enable();
+                        }
+                        let mod_rm = encoder.mod_rm.get_or_insert_with(ModRm::new);
+                        mod_rm.set_mod(0b11);
+                        mod_rm.set_rm(index & 0b111);
+                        encoder.rex_prefix.set_b(index >= 8);
+                    } else {
+                        let memory_expr = get_rm64_ref_str(operand).ok_or_else(|| mismatch("rm64"))?;
+                        encode_memory_operand(memory_expr, &mut encoder).ok_or_else(|| mismatch("rm64"))?;
+                    }
+                }
+                OperandType::Imm64 => {
+                    encoder.imm = Imm::Imm64(stoi(operand).ok_or_else(|| mismatch("imm64"))? as u64);
+                }
+                // the real displacement is patched in later by
+                // `crate::assembler`; which width to reserve here comes
+                // from `rule.imm`, not from the operand type (a `Rel32`
+                // operand can still be a short `Rel8` jump)
+                OperandType::Rel32 => {
+                    encoder.imm = match rule.imm {
+                        ImmRule::Rel8 => Imm::Imm8(0),
+                        _ => Imm::Imm32(0),
+                    };
+                }
+                OperandType::None => {}
+            }
+        }
+
+        if let ModRmRule::Digit(digit) = rule.modrm {
+            encoder.mod_rm.get_or_insert_with(ModRm::new).set_reg(digit);
+        }
+
+        Ok(Some(encoder))
+    }
+}
+
+/// the canonical x64 register names in encoding order (`rax` = 0 .. `r15` = 15)
+const REGISTER_NAMES: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+
+/// `REGISTER_NAMES`'s entries, in the same order, as `Register` variants
+const REGISTERS: [Register; 16] = [
+    Register::Rax,
+    Register::Rcx,
+    Register::Rdx,
+    Register::Rbx,
+    Register::Rsp,
+    Register::Rbp,
+    Register::Rsi,
+    Register::Rdi,
+    Register::R8,
+    Register::R9,
+    Register::R10,
+    Register::R11,
+    Register::R12,
+    Register::R13,
+    Register::R14,
+    Register::R15,
+];
+
+fn register_index(name: &str) -> Option<u8> {
+    let position = REGISTER_NAMES.iter().position(|candidate| *candidate == name)?;
+    Some(REGISTERS[position].index())
+}
+
+/// encode a `[base + index*scale + disp]`-style memory reference (already
+/// stripped of its brackets by `get_rm64_ref_str`) into `encoder`'s ModRM,
+/// SIB and displacement fields
+/// # Supported forms
+/// - `[reg]`, `[reg + disp]`
+/// - `[base + index*scale]`, `[base + index*scale + disp]`
+/// - `[rip + disp]` (RIP-relative, disp defaults to 0)
+/// `scale` must be one of `1`, `2`, `4`, `8`; `index` may not be `rsp`
+fn encode_memory_operand(expr: &str, encoder: &mut Encoder) -> Option<()> {
+    let mut rip_relative = false;
+    let mut base = None;
+    let mut index = None;
+    let mut scale = 0u8;
+    let mut disp: Option<i64> = None;
+
+    for term in expr.split('+').map(str::trim) {
+        if term == "rip" {
+            rip_relative = true;
+        } else if let Some((reg, scale_str)) = term.split_once('*') {
+            let reg = register_index(get_reg64_str(reg.trim())?)?;
+            if reg == 4 {
+                // rsp can't be an index register
+                return None;
+            }
+            index = Some(reg);
+            scale = match scale_str.trim() {
+                "1" => 0,
+                "2" => 1,
+                "4" => 2,
+                "8" => 3,
+                _ => return None,
+            };
+        } else if let Some(name) = get_reg64_str(term) {
+            base = Some(register_index(name)?);
+        } else {
+            disp = Some(stoi(term)?);
+        }
+    }
+
+    // keep a `reg` field an earlier operand (or `/digit`) already set
+    let mut mod_rm = encoder.mod_rm.unwrap_or_else(ModRm::new);
+
+    if rip_relative {
+        mod_rm.set_mod(0b00);
+        mod_rm.set_rm(0b101);
+        encoder.disp = Disp::Disp32(disp.unwrap_or(0) as i32 as u32);
+    } else {
+        let base = base?;
+        let needs_sib = index.is_some() || base & 0b111 == 0b100;
+
+        if needs_sib {
+            mod_rm.set_rm(0b100);
+            let mut sib = Sib::new();
+            sib.set_scale(scale);
+            sib.set_index(index.map(|i| i & 0b111).unwrap_or(0b100));
+            sib.set_base(base & 0b111);
+            encoder.sib = Some(sib);
+            if let Some(index) = index {
+                if index >= 8 {
+                    encoder.rex_prefix.enable();
+                }
+                encoder.rex_prefix.set_x(index >= 8);
+            }
+        }
+        if base >= 8 {
+            encoder.rex_prefix.enable();
+        }
+        encoder.rex_prefix.set_b(base >= 8);
+        if !needs_sib {
+            mod_rm.set_rm(base & 0b111);
+        }
+
+        // rbp/r13 with mod=00 collides with the RIP-relative encoding (no
+        // SIB) or the no-base encoding (SIB), so a zero displacement must be
+        // spelled out
+        let base_needs_disp8 = base & 0b111 == 0b101;
+
+        match disp {
+            Some(d) if d == 0 && !base_needs_disp8 => mod_rm.set_mod(0b00),
+            Some(d) if i8::try_from(d).is_ok() => {
+                mod_rm.set_mod(0b01);
+                encoder.disp = Disp::Disp8(d as i8 as u8);
+            }
+            Some(d) => {
+                mod_rm.set_mod(0b10);
+                encoder.disp = Disp::Disp32(d as i32 as u32);
+            }
+            None if base_needs_disp8 => {
+                mod_rm.set_mod(0b01);
+                encoder.disp = Disp::Disp8(0);
+            }
+            None => mod_rm.set_mod(0b00),
+        }
     }
 
-    pub fn modrm_mode(&self) -> Option<ModRmMode> {
-        Some(
-            match self.ops?.0.encoding_rule.modrm_mode
-        )
-    }*/
+    encoder.mod_rm = Some(mod_rm);
+    Some(())
+}
+
+#[cfg(test)]
+mod memory_operand_tests {
+    use super::*;
+
+    fn mod_rm(encoder: &Encoder) -> (u8, u8, u8) {
+        let byte = encoder.mod_rm.expect("ModRM should be set").get();
+        (byte >> 6 & 0b11, byte >> 3 & 0b111, byte & 0b111)
+    }
+
+    #[test]
+    fn rbp_with_no_displacement_is_forced_to_disp8_zero() {
+        // [rbp]: mod=00/rm=101 would collide with RIP-relative addressing,
+        // so a zero disp8 must be spelled out instead
+        let mut encoder = Encoder::new();
+        encode_memory_operand("rbp", &mut encoder).expect("should encode");
+
+        assert_eq!(mod_rm(&encoder), (0b01, 0, 0b101));
+        assert!(matches!(encoder.disp, Disp::Disp8(0)));
+        assert!(encoder.sib.is_none());
+    }
+
+    #[test]
+    fn rsp_base_requires_a_sib_byte() {
+        // rsp/r12 can never be a bare ModRM rm field; a SIB with no index is required
+        let mut encoder = Encoder::new();
+        encode_memory_operand("rsp", &mut encoder).expect("should encode");
+
+        let (r#mod, _, rm) = mod_rm(&encoder);
+        assert_eq!((r#mod, rm), (0b00, 0b100));
+        let sib = encoder.sib.expect("SIB should be set");
+        assert_eq!(sib.get() & 0b111, 0b100); // base = rsp
+        assert_eq!(sib.get() >> 3 & 0b111, 0b100); // index = none
+    }
+
+    #[test]
+    fn rsp_cannot_be_an_index_register() {
+        let mut encoder = Encoder::new();
+        assert!(encode_memory_operand("rax + rsp*2", &mut encoder).is_none());
+    }
+
+    #[test]
+    fn rip_relative_uses_disp32_regardless_of_size() {
+        let mut encoder = Encoder::new();
+        encode_memory_operand("rip + 16", &mut encoder).expect("should encode");
+
+        assert_eq!(mod_rm(&encoder), (0b00, 0, 0b101));
+        assert!(matches!(encoder.disp, Disp::Disp32(16)));
+        assert!(encoder.sib.is_none());
+    }
+
+    #[test]
+    fn extended_base_register_sets_rex_b() {
+        // [r12 + 8]: r12 needs a SIB (low 3 bits == rsp's) and sets REX.B;
+        // `Rex`'s bits are private, so check the assembled bytes instead --
+        // rex(0x41), mod_rm(0x44), sib(0x24), disp8(0x08)
+        let mut encoder = Encoder::new();
+        encode_memory_operand("r12 + 8", &mut encoder).expect("should encode");
+
+        assert_eq!(&*encoder.encode(), &[0x41, 0x44, 0x24, 0x08]);
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -52,42 +334,48 @@ impl<'a> RowLine<'a> {
         }
     }
 
-    pub fn to_line(&self, operators_list: &[Operator]) -> Option<Line<'a>> {
+    pub fn to_line(&self, operators_list: &[Operator]) -> Result<Line<'a>, AsmError<'a>> {
         if self.mnemonic.is_some() {
-            Some(Line {
+            let index = self.get_operation_index(operators_list)?;
+            Ok(Line {
                 label: self.label,
-                ops: Some((
-                    operators_list[self.get_operation_index(operators)?],
-                    self.operands,
-                )),
+                ops: Some((operators_list[index], self.operands)),
             })
         } else {
-            Some(Line {
+            Ok(Line {
                 label: self.label,
                 ops: None,
             })
         }
     }
 
-    pub fn get_operation_index(self, operators_list: &[Operator]) -> Option<usize> {
+    pub fn get_operation_index(self, operators_list: &[Operator]) -> Result<usize, AsmError<'a>> {
+        let mnemonic = self.mnemonic.ok_or(AsmError::UnknownMnemonic(""))?;
+        let mut mismatch = None;
+
         for i in 0..operators_list.len() {
-            if self.mnemonic.is_some()
-                && self.mnemonic? == operators_list[i].mnemonic
-                && operators_list[i].operands.len() == self.operands.len()
-            {
-                let mut flag = true;
-                for k in 0..operators_list[i].operands.len() {
-                    if !operators_list[i].operands[k].is_match(self.operands[k]) {
-                        flag = false;
-                        break;
-                    }
-                }
-                if flag {
-                    return Some(i);
+            if mnemonic != operators_list[i].mnemonic || operators_list[i].operands.len() != self.operands.len() {
+                continue;
+            }
+
+            let mut matched = true;
+            for k in 0..operators_list[i].operands.len() {
+                if !operators_list[i].operands[k].is_match(self.operands[k]) {
+                    matched = false;
+                    mismatch = Some(AsmError::OperandTypeMismatch {
+                        position: k,
+                        expected: operators_list[i].operands[k].name(),
+                        found: self.operands[k],
+                    });
+                    break;
                 }
             }
+            if matched {
+                return Ok(i);
+            }
         }
-        return None;
+
+        Err(mismatch.unwrap_or(AsmError::UnknownMnemonic(mnemonic)))
     }
 }
 
@@ -98,74 +386,70 @@ pub struct Operator {
     encoding_rule: Rule,
 }
 
-pub static operators: &[Operator] = &[
-    Operator {
-        mnemonic: "mov",
-        operands: SVec::value([OperandType::Reg64, OperandType::Imm64], 2),
-        encoding_rule: Rule {
-            opecode: SVec::value([0xb8, 0, 0], 1),
-            rex: RexRule::RexW,
-            modrm: ModRmRule::None,
-            imm: ImmRule::Id,
-            add_reg: AddRegRule::Rd,
-        },
-    },
-    Operator {
-        mnemonic: "mov",
-        operands: SVec::value([OperandType::Reg64, OperandType::Rm64], 2),
-        encoding_rule: Rule {
-            opecode: SVec::value([0x8b, 0, 0], 1),
-            rex: RexRule::RexW,
-            modrm: ModRmRule::R,
-            imm: ImmRule::None,
-            add_reg: AddRegRule::None,
-        },
-    }, //50+rd PUSH r64
-    Operator {
-        mnemonic: "push",
-        operands: SVec::value([OperandType::Reg64, OperandType::None], 1),
-        encoding_rule: Rule {
-            opecode: SVec::value([0x50, 0, 0], 1),
-            rex: RexRule::None,
-            modrm: ModRmRule::None,
-            imm: ImmRule::None,
-            add_reg: AddRegRule::Rd,
-        },
-    }, //REX.W + 58+ rd POP r64
-    Operator {
-        mnemonic: "pop",
-        operands: SVec::value([OperandType::Reg64, OperandType::None], 1),
-        encoding_rule: Rule {
-            opecode: SVec::value([0x58, 0, 0], 1),
-            rex: RexRule::RexW,
-            modrm: ModRmRule::None,
-            imm: ImmRule::None,
-            add_reg: AddRegRule::Rd,
-        },
-    }, //C3 RET
-    Operator {
-        mnemonic: "ret",
-        operands: SVec::value([OperandType::None, OperandType::None], 0),
-        encoding_rule: Rule {
-            opecode: SVec::value([0xc3, 0, 0], 1),
-            rex: RexRule::None,
-            modrm: ModRmRule::None,
-            imm: ImmRule::None,
-            add_reg: AddRegRule::None,
-        },
-    },
-];
+impl Operator {
+    pub(crate) fn mnemonic(&self) -> &'static str {
+        self.mnemonic
+    }
 
-#[derive(Clone, Copy, Default, Debug)]
-enum OperandType {
-    #[default]
-    None,
-    Imm64,
-    Reg64,
-    Rm64,
+    pub(crate) fn encoding_rule(&self) -> Rule {
+        self.encoding_rule
+    }
+}
+
+/// Reverse-look-up the `operators` table for the entry whose opcode bytes
+/// match `opecode`, honoring `AddRegRule` (the `+rd` family encodes a
+/// register in the low 3 bits of the final opcode byte).
+pub(crate) fn find_operator(opecode: &Opecode) -> Option<Operator> {
+    'operators: for operator in operators {
+        let rule = operator.encoding_rule();
+        if rule.opecode.len() != opecode.len() {
+            continue;
+        }
+
+        for i in 0..opecode.len() - 1 {
+            if rule.opecode[i] != opecode[i] {
+                continue 'operators;
+            }
+        }
+
+        let last = opecode.len() - 1;
+        let last_matches = match rule.add_reg {
+            AddRegRule::None => rule.opecode[last] == opecode[last],
+            _ => rule.opecode[last] == opecode[last] & !0b111,
+        };
+        if last_matches {
+            return Some(*operator);
+        }
+    }
+    None
+}
+
+/// Decode a single instruction from `bytes` and resolve it back to the
+/// `Operator` that produced it.
+/// Returns the matched mnemonic, the decoded `Encoder`, and the number of
+/// bytes consumed.
+pub fn disassemble(bytes: &[u8]) -> Option<(&'static str, Encoder, usize)> {
+    let (encoder, len) = Encoder::decode(bytes)?;
+    let operator = find_operator(&encoder.opecode)?;
+    Some((operator.mnemonic(), encoder, len))
 }
 
+// `operators` and `OperandType` are generated from `instructions.in` by
+// build.rs; see that file for the spec format.
+include!(concat!(env!("OUT_DIR"), "/operators.rs"));
+
 impl OperandType {
+    /// the operand-type name used in `AsmError::OperandTypeMismatch` diagnostics
+    fn name(self) -> &'static str {
+        match self {
+            OperandType::None => "none",
+            OperandType::Imm64 => "imm64",
+            OperandType::Reg64 => "r64",
+            OperandType::Rm64 => "rm64",
+            OperandType::Rel32 => "rel32",
+        }
+    }
+
     fn is_match(self, expr: &str) -> bool {
         match self {
             OperandType::None => {
@@ -178,12 +462,20 @@ impl OperandType {
             OperandType::Imm64 => stoi(expr).is_some(),
             OperandType::Reg64 => get_reg64_str(expr).is_some(),
             OperandType::Rm64 => get_reg64_str(expr).is_some() || get_rm64_ref_str(expr).is_some(),
+            // a rel32 operand is always a label reference: anything that
+            // isn't itself a register, a memory operand, or an immediate
+            OperandType::Rel32 => {
+                !expr.is_empty()
+                    && stoi(expr).is_none()
+                    && get_reg64_str(expr).is_none()
+                    && get_rm64_ref_str(expr).is_none()
+            }
         }
     }
 }
 
 #[derive(Clone, Copy, Debug)]
-struct Rule {
+pub(crate) struct Rule {
     pub opecode: SVec<3, u8>,
     pub rex: RexRule,
     pub modrm: ModRmRule,
@@ -193,12 +485,7 @@ struct Rule {
 
 pub type RexRule = RexMode;
 
-#[derive(Clone, Copy, Debug)]
-pub enum ModRmRule {
-    None,
-    R,
-    Dight(u8),
-}
+pub type ModRmRule = ModRmMode;
 
 #[derive(Clone, Copy, Debug)]
 pub enum ImmRule {
@@ -207,6 +494,12 @@ pub enum ImmRule {
     Iw,
     Id,
     Io,
+    /// a rel8 displacement, relative to the end of the instruction,
+    /// patched in during label resolution (see `crate::assembler`)
+    Rel8,
+    /// a rel32 displacement, relative to the end of the instruction,
+    /// patched in during label resolution (see `crate::assembler`)
+    Rel32,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -0,0 +1,4 @@
+//! types shared between `instructions.in`'s generated `operators` table and
+//! the code that consumes it
+
+pub mod raw_encoder;
@@ -0,0 +1,23 @@
+//! the small encoding-mode enums an `Operator`'s `Rule` is built from
+
+/// which REX prefix, if any, an instruction always forces
+#[derive(Clone, Copy, Debug)]
+pub enum RexMode {
+    /// no REX prefix is forced (one may still be added for an extended register)
+    None,
+    /// REX prefix forced, W clear
+    Rex,
+    /// REX.W prefix forced (64-bit operand size)
+    RexW,
+}
+
+/// how an instruction's ModRM byte, if any, is filled in
+#[derive(Clone, Copy, Debug)]
+pub enum ModRmMode {
+    /// no ModRM byte
+    None,
+    /// `/r`: the ModRM `reg` field holds another operand's register
+    R,
+    /// `/digit`: the ModRM `reg` field holds a fixed opcode-extension digit
+    Digit(u8),
+}
@@ -0,0 +1,30 @@
+//! the x64 general-purpose register set
+
+/// An x64 general-purpose register, in encoding order (`Rax` = 0 .. `R15` = 15).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    Rax,
+    Rcx,
+    Rdx,
+    Rbx,
+    Rsp,
+    Rbp,
+    Rsi,
+    Rdi,
+    R8,
+    R9,
+    R10,
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+}
+
+impl Register {
+    /// this register's 4-bit encoding, split across ModRM/SIB and the REX
+    /// extension bit by callers
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+}
@@ -0,0 +1,24 @@
+/// Crate-wide error type for everything that can go wrong turning source
+/// text into machine code: an unmatched mnemonic, a mismatched operand, a
+/// fixed-capacity buffer that ran out of room, an immediate that doesn't
+/// fit its field, or an undefined label. Carries the offending source
+/// substring (or operand position) so callers get an actionable
+/// diagnostic instead of a silent `None` or a process abort.
+#[derive(Clone, Copy, Debug)]
+pub enum AsmError<'a> {
+    /// no entry in the `operators` table has this mnemonic with this many
+    /// operands
+    UnknownMnemonic(&'a str),
+    /// an operand didn't match the type the matched mnemonic expects
+    OperandTypeMismatch {
+        position: usize,
+        expected: &'static str,
+        found: &'a str,
+    },
+    /// a fixed-capacity `SVec` ran out of room
+    BufferOverflow,
+    /// an immediate or relocation delta didn't fit in its encoded width
+    ImmediateOutOfRange(&'a str),
+    /// a `Rel8`/`Rel32` operand referenced a label that was never defined
+    UndefinedLabel(&'a str),
+}
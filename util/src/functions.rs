@@ -0,0 +1,44 @@
+//! small string/number parsing helpers the line parser relies on
+
+/// Parse a decimal or `0x`-prefixed hexadecimal integer, with an optional
+/// leading `-`.
+pub fn stoi(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else {
+        digits.parse::<i64>().ok()?
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+/// If `s` (once trimmed) is wrapped in `open`/`close`, return its trimmed
+/// inner contents; used to strip the brackets off a `[...]` memory operand.
+pub fn get_inner_expr(s: &str, open: char, close: char) -> Option<&str> {
+    let s = s.trim();
+    let inner = s.strip_prefix(open)?.strip_suffix(close)?;
+    Some(inner.trim())
+}
+
+/// The outcome of matching a literal prefix off the front of a `&str`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchStr<'a> {
+    /// the pattern matched; `rest` is what follows it
+    Matched { rest: &'a str },
+    NotMatched,
+}
+
+/// Match `pattern` against the start of `s` (after trimming leading
+/// whitespace), returning what's left over on success.
+pub fn match_str<'a>(s: &'a str, pattern: &str) -> MatchStr<'a> {
+    match s.trim_start().strip_prefix(pattern) {
+        Some(rest) => MatchStr::Matched { rest },
+        None => MatchStr::NotMatched,
+    }
+}
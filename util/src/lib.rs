@@ -0,0 +1,9 @@
+#![no_std]
+
+//! `util` holds small, dependency-free building blocks shared across the
+//! crate: `SVec`, a stack-only fixed-capacity vector, and the string/number
+//! helpers the assembler's line parser relies on.
+
+pub mod error;
+pub mod functions;
+pub mod svec;
@@ -1,7 +1,9 @@
-use std::convert::From;
-use std::fmt::{Display, Error, Formatter};
-use std::iter::{IntoIterator, Iterator};
-use std::ops::{Deref, DerefMut};
+use core::convert::From;
+use core::fmt::{Display, Error, Formatter};
+use core::iter::{IntoIterator, Iterator};
+use core::ops::{Deref, DerefMut};
+
+use crate::error::AsmError;
 
 /// SVec is a vector collection type using only stack.
 /// # Feature
@@ -44,6 +46,15 @@ impl<const C: usize, T: Copy + Default> SVec<C, T> {
         }
     }
 
+    /// Construct an SVec directly from a fixed-size array and a length,
+    /// treating only its first `len` elements as valid; used by generated
+    /// code (see `asm`'s build.rs) that builds a `static` table and can't
+    /// call `push` in that context
+    pub const fn value(array: [T; C], len: usize) -> Self {
+        assert!(len <= C, "length exceeds capacity");
+        SVec { array, len }
+    }
+
     /// Push value to SVec
     pub fn push(&mut self, value: T) -> &mut SVec<C, T> {
         if self.len() == C {
@@ -82,6 +93,38 @@ impl<const C: usize, T: Copy + Default> SVec<C, T> {
         }
     }
 
+    /// Push value to SVec, returning `Err` instead of panicking on overflow
+    pub fn try_push(&mut self, value: T) -> Result<&mut SVec<C, T>, AsmError<'static>> {
+        if self.len() == C {
+            Err(AsmError::BufferOverflow)
+        } else {
+            Ok(self.push(value))
+        }
+    }
+
+    /// Resize SVec, returning `Err` instead of panicking on overflow
+    pub fn try_resize(&mut self, len: usize) -> Result<&mut SVec<C, T>, AsmError<'static>> {
+        if C < len {
+            Err(AsmError::BufferOverflow)
+        } else {
+            Ok(self.resize(len))
+        }
+    }
+
+    /// Construct an SVec from a slice, returning `Err` instead of panicking
+    /// when the slice is longer than the capacity
+    pub fn try_from_slice(value: &[T]) -> Result<SVec<C, T>, AsmError<'static>> {
+        if C < value.len() {
+            return Err(AsmError::BufferOverflow);
+        }
+
+        let mut new_svec = SVec::new();
+        for i in 0..value.len() {
+            new_svec.push(value[i]);
+        }
+        Ok(new_svec)
+    }
+
     /// Get capacity
     pub fn capacity(&self) -> usize {
         C